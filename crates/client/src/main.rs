@@ -1,14 +1,26 @@
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
 
-use common::{Message, Network, NetworkPlugin};
+use serde::{Deserialize, Serialize};
+
+use common::{impl_packet, Message, Network, NetworkPlugin, PacketEvent};
+
+/// A typed chat packet, demonstrating `NetworkPlugin::register_packet` alongside the raw
+/// `Network::try_recv`/`try_send` API used for the "message!"/"reply!" traffic below.
+#[derive(Serialize, Deserialize)]
+struct ChatMessage {
+    text: String,
+}
+
+impl_packet!(ChatMessage);
 
 fn main() {
     let mut app = App::new();
     app.add_plugins(MinimalPlugins);
     app.add_plugin(LogPlugin::default());
-    app.add_plugin(NetworkPlugin::new(0)); // Pick no listen port since we're a client
+    app.add_plugin(NetworkPlugin::new(0).register_packet::<ChatMessage>()); // Pick no listen port since we're a client
     app.add_system(print_network_messages);
+    app.add_system(print_chat_messages);
     app.add_system(send_client_message);
     app.run();
 }
@@ -22,10 +34,28 @@ fn print_network_messages(net: Res<Network>) {
     }
 }
 
+fn print_chat_messages(mut packets: EventReader<PacketEvent<ChatMessage>>) {
+    for packet in packets.iter() {
+        info!(
+            "got chat: \"{}\" from: {}",
+            packet.packet().text,
+            packet.address()
+        );
+    }
+}
+
 fn send_client_message(net: Res<Network>) {
     let target_addr = Network::parse_socket_addr("127.0.0.1:34243");
+
     let reply = Message::new(target_addr, "message!".as_bytes().to_vec());
     if net.try_send(reply).is_err() {
         warn!("failed to send message");
     }
+
+    let chat = ChatMessage {
+        text: "hello from client".to_string(),
+    };
+    if net.send_packet(target_addr, &chat).is_err() {
+        warn!("failed to send chat packet");
+    }
 }
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use bevy::ecs::world::World;
+use bevy::prelude::*;
+
+use async_net::SocketAddr;
+
+use crate::dispatch::CHANNEL_CONNECTION;
+use crate::{Network, NetworkPlugin};
+
+/// Default interval between heartbeat pings sent to each tracked peer.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default time a peer may go unheard from before its connection is reaped.
+pub const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Marks an entity as a tracked peer, keyed off the [`SocketAddr`] datagrams arrive from.
+pub struct Connection {
+    address: SocketAddr,
+    last_seen: Instant,
+    rtt: Option<Duration>,
+}
+
+impl Connection {
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    pub fn last_seen(&self) -> Instant {
+        self.last_seen
+    }
+
+    /// Round trip time measured from the most recent heartbeat, if one has completed yet.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+}
+
+/// Fired when a peer is first seen, or reaped after exceeding the connection timeout.
+pub enum ConnectionEvent {
+    Connected(Entity, SocketAddr),
+    Disconnected(Entity, SocketAddr),
+}
+
+/// Looks up the tracked entity for a previously seen peer address.
+#[derive(Default)]
+struct ConnectionRegistry {
+    by_address: HashMap<SocketAddr, Entity>,
+}
+
+const HEARTBEAT_PING: u8 = 0;
+const HEARTBEAT_PONG: u8 = 1;
+const HEARTBEAT_SIZE: usize = 1 + 4;
+
+/// Outstanding pings we've sent, keyed by id, so a returning pong can be timed.
+#[derive(Default)]
+struct HeartbeatState {
+    next_ping_id: u32,
+    pending: HashMap<u32, Instant>,
+}
+
+struct HeartbeatConfig {
+    interval: Duration,
+}
+
+struct ConnectionTimeoutConfig {
+    timeout: Duration,
+}
+
+/// Called by the shared dispatch system for every dispatched message, regardless of channel:
+/// any traffic from a peer counts as presence, not just its heartbeats. Spawns a [`Connection`]
+/// the first time an address is seen, or refreshes its last-seen time otherwise.
+pub(crate) fn touch(world: &mut World, address: SocketAddr) {
+    let now = Instant::now();
+
+    let spawned = world.resource_scope(|world, mut registry: Mut<ConnectionRegistry>| {
+        if registry.by_address.contains_key(&address) {
+            None
+        } else {
+            let entity = world
+                .spawn(Connection {
+                    address,
+                    last_seen: now,
+                    rtt: None,
+                })
+                .id();
+            registry.by_address.insert(address, entity);
+            Some(entity)
+        }
+    });
+
+    match spawned {
+        Some(entity) => world
+            .resource_mut::<Events<ConnectionEvent>>()
+            .send(ConnectionEvent::Connected(entity, address)),
+        None => {
+            let entity = world.resource::<ConnectionRegistry>().by_address[&address];
+            if let Some(mut connection) = world.get_mut::<Connection>(entity) {
+                connection.last_seen = now;
+            }
+        }
+    }
+}
+
+/// Routes a single message dispatched on the connection channel: answers pings with a pong,
+/// and times pongs against the ping they answer to update the peer's RTT estimate.
+pub(crate) fn route_heartbeat(world: &mut World, address: SocketAddr, body: &[u8]) {
+    if body.len() != HEARTBEAT_SIZE || !matches!(body[0], HEARTBEAT_PING | HEARTBEAT_PONG) {
+        return;
+    }
+
+    let tag = body[0];
+    let ping_id = u32::from_le_bytes(body[1..HEARTBEAT_SIZE].try_into().unwrap());
+
+    match tag {
+        HEARTBEAT_PING => {
+            let mut pong = vec![HEARTBEAT_PONG];
+            pong.extend(ping_id.to_le_bytes());
+            let net = world.resource::<Network>();
+            let _ = net.try_send_tagged(address, CHANNEL_CONNECTION, pong);
+        }
+        HEARTBEAT_PONG => {
+            let Some(sent_at) = world
+                .resource_mut::<HeartbeatState>()
+                .pending
+                .remove(&ping_id)
+            else {
+                return;
+            };
+            let Some(&entity) = world
+                .resource::<ConnectionRegistry>()
+                .by_address
+                .get(&address)
+            else {
+                return;
+            };
+            if let Some(mut connection) = world.get_mut::<Connection>(entity) {
+                connection.rtt = Some(Instant::now().saturating_duration_since(sent_at));
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Periodically pings every tracked peer so idle connections still produce last-seen traffic
+/// and an up to date RTT measurement.
+fn send_heartbeats(
+    time: Res<Time>,
+    mut timer: Local<Option<Timer>>,
+    net: Res<Network>,
+    mut heartbeats: ResMut<HeartbeatState>,
+    config: Res<HeartbeatConfig>,
+    timeout_config: Res<ConnectionTimeoutConfig>,
+    connections: Query<&Connection>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::new(config.interval, TimerMode::Repeating));
+    timer.tick(time.delta());
+    if !timer.just_finished() {
+        return;
+    }
+
+    let now = Instant::now();
+
+    // A pong that never arrives (lost packet, or the peer disconnected) would otherwise leave
+    // its entry in `pending` forever; prune anything older than the connection timeout.
+    heartbeats
+        .pending
+        .retain(|_, sent_at| now.saturating_duration_since(*sent_at) < timeout_config.timeout);
+
+    for connection in &connections {
+        let ping_id = heartbeats.next_ping_id;
+        heartbeats.next_ping_id = heartbeats.next_ping_id.wrapping_add(1);
+        heartbeats.pending.insert(ping_id, now);
+
+        let mut payload = vec![HEARTBEAT_PING];
+        payload.extend(ping_id.to_le_bytes());
+        let _ = net.try_send_tagged(*connection.address(), CHANNEL_CONNECTION, payload);
+    }
+}
+
+/// Reaps connections that haven't been heard from within the configured timeout, despawning
+/// the entity so game logic can observe the drop via `RemovedComponents<Connection>`.
+fn reap_stale_connections(
+    mut commands: Commands,
+    mut registry: ResMut<ConnectionRegistry>,
+    mut events: EventWriter<ConnectionEvent>,
+    config: Res<ConnectionTimeoutConfig>,
+    connections: Query<(Entity, &Connection)>,
+) {
+    let now = Instant::now();
+
+    for (entity, connection) in &connections {
+        if now.saturating_duration_since(connection.last_seen) > config.timeout {
+            registry.by_address.remove(connection.address());
+            events.send(ConnectionEvent::Disconnected(entity, *connection.address()));
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+impl NetworkPlugin {
+    /// Overrides how often a heartbeat ping is sent to each tracked peer. Default: 1 second.
+    pub fn with_heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// Overrides how long a peer may go unheard from before its connection is reaped.
+    /// Default: 5 seconds.
+    pub fn with_connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_timeout = timeout;
+        self
+    }
+
+    pub(crate) fn build_connections(&self, app: &mut App) {
+        app.insert_resource(ConnectionRegistry::default())
+            .insert_resource(HeartbeatState::default())
+            .insert_resource(HeartbeatConfig {
+                interval: self.heartbeat_interval,
+            })
+            .insert_resource(ConnectionTimeoutConfig {
+                timeout: self.connection_timeout,
+            })
+            .add_event::<ConnectionEvent>()
+            .add_system(send_heartbeats)
+            .add_system(reap_stale_connections);
+    }
+}
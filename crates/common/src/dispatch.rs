@@ -0,0 +1,74 @@
+use bevy::ecs::world::World;
+use bevy::log::warn;
+use bevy::prelude::App;
+
+use crate::{connection, packet, reliability, Message, Network, NetworkPlugin};
+
+/// Size in bytes of the channel tag prepended to every message handed to [`dispatch_incoming`].
+const CHANNEL_TAG_SIZE: usize = 1;
+
+/// Plain application traffic with no subsystem header, left for `Network::try_recv` once
+/// dispatched.
+pub(crate) const CHANNEL_RAW: u8 = 0;
+/// Traffic routed to a registered [`crate::Packet`] type's [`crate::PacketEvent`].
+pub(crate) const CHANNEL_PACKET: u8 = 1;
+/// Traffic routed to the [`crate::Reliability`] layer.
+pub(crate) const CHANNEL_RELIABILITY: u8 = 2;
+/// Connection tracking's own heartbeat ping/pong traffic.
+pub(crate) const CHANNEL_CONNECTION: u8 = 3;
+
+/// Prepends a channel tag to `payload`, so [`dispatch_incoming`] can route it without the
+/// subsystems racing each other for the same underlying receive channel.
+pub(crate) fn wrap(channel: u8, payload: Vec<u8>) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(CHANNEL_TAG_SIZE + payload.len());
+    buf.push(channel);
+    buf.extend(payload);
+    buf
+}
+
+/// Splits the channel tag off the front of a dispatched payload.
+fn unwrap_channel(payload: &[u8]) -> Option<(u8, &[u8])> {
+    payload.split_first().map(|(&tag, rest)| (tag, rest))
+}
+
+/// The single consumer of `Network`'s receive channel. Every other subsystem (typed packets,
+/// reliability, connection tracking) is handed its messages from here instead of draining the
+/// channel itself, so no two subsystems can race for, or silently discard, the same traffic.
+fn dispatch_incoming(world: &mut World) {
+    let messages: Vec<Message> = {
+        let net = world.resource::<Network>();
+        std::iter::from_fn(|| net.poll_channel()).collect()
+    };
+
+    for message in messages {
+        let address = *message.address();
+        let Some((channel, body)) = unwrap_channel(message.payload()) else {
+            continue;
+        };
+
+        connection::touch(world, address);
+
+        match channel {
+            CHANNEL_RAW => {
+                let payload = body.to_vec();
+                world
+                    .resource::<Network>()
+                    .push_raw(Message::new(address, payload));
+            }
+            CHANNEL_PACKET => packet::route(world, address, body),
+            CHANNEL_RELIABILITY => reliability::route(world, address, body),
+            CHANNEL_CONNECTION => connection::route_heartbeat(world, address, body),
+            _ => warn!(
+                "dropped message with unknown channel tag {} from {}",
+                channel, address
+            ),
+        }
+    }
+}
+
+impl NetworkPlugin {
+    /// Wires the shared dispatch system that every other `build_*` step routes traffic through.
+    pub(crate) fn build_dispatch(&self, app: &mut App) {
+        app.add_system(dispatch_incoming);
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use async_net::SocketAddr;
+
+/// Default maximum fragment size, kept comfortably under typical path MTU.
+pub const DEFAULT_FRAGMENT_MTU: usize = 1200;
+
+/// How long an incomplete reassembly is kept before being dropped.
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const MARKER_WHOLE: u8 = 0;
+const MARKER_FRAGMENT: u8 = 1;
+
+/// Size in bytes of the header carried by each fragment, after the marker byte.
+const FRAGMENT_HEADER_SIZE: usize = 4 + 2 + 2; // message id + fragment index + fragment count
+
+static NEXT_MESSAGE_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Splits `payload` into datagrams carrying at most `mtu` payload bytes each, every one tagged
+/// with a one-byte marker so whole (unfragmented) messages only pay a single byte of overhead.
+/// Fragments also carry a [`FRAGMENT_HEADER_SIZE`]-byte header after the marker, so a datagram
+/// can be up to `mtu + 1 + FRAGMENT_HEADER_SIZE` bytes long.
+pub fn split_payload(payload: &[u8], mtu: usize) -> Vec<Vec<u8>> {
+    if payload.len() <= mtu {
+        let mut datagram = Vec::with_capacity(1 + payload.len());
+        datagram.push(MARKER_WHOLE);
+        datagram.extend_from_slice(payload);
+        return vec![datagram];
+    }
+
+    let message_id = NEXT_MESSAGE_ID.fetch_add(1, Ordering::Relaxed);
+    let chunks: Vec<&[u8]> = payload.chunks(mtu).collect();
+    let fragment_count = chunks.len() as u16;
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut datagram = Vec::with_capacity(1 + FRAGMENT_HEADER_SIZE + chunk.len());
+            datagram.push(MARKER_FRAGMENT);
+            datagram.extend(message_id.to_le_bytes());
+            datagram.extend((index as u16).to_le_bytes());
+            datagram.extend(fragment_count.to_le_bytes());
+            datagram.extend_from_slice(chunk);
+            datagram
+        })
+        .collect()
+}
+
+struct PendingReassembly {
+    fragments: HashMap<u16, Vec<u8>>,
+    fragment_count: u16,
+    first_seen: Instant,
+}
+
+/// Per-socket reassembly state, keyed by the (source, message id) pair fragments arrive under.
+#[derive(Default)]
+pub struct ReassemblyTable {
+    pending: HashMap<(SocketAddr, u32), PendingReassembly>,
+}
+
+impl ReassemblyTable {
+    /// Feeds a raw datagram in. Returns the reassembled payload once all of its fragments
+    /// (or the whole payload immediately, for unfragmented datagrams) have arrived.
+    pub fn receive(&mut self, source: SocketAddr, datagram: &[u8]) -> Option<Vec<u8>> {
+        let (&marker, rest) = datagram.split_first()?;
+        match marker {
+            MARKER_WHOLE => Some(rest.to_vec()),
+            MARKER_FRAGMENT => self.receive_fragment(source, rest),
+            _ => None,
+        }
+    }
+
+    fn receive_fragment(&mut self, source: SocketAddr, rest: &[u8]) -> Option<Vec<u8>> {
+        if rest.len() < FRAGMENT_HEADER_SIZE {
+            return None;
+        }
+
+        let message_id = u32::from_le_bytes(rest[0..4].try_into().unwrap());
+        let index = u16::from_le_bytes(rest[4..6].try_into().unwrap());
+        let fragment_count = u16::from_le_bytes(rest[6..8].try_into().unwrap());
+        let chunk = &rest[FRAGMENT_HEADER_SIZE..];
+
+        // A fragment_count of 0 can't legitimately describe any fragmented message (see
+        // split_payload); reject it rather than treating the first fragment as already complete.
+        if fragment_count == 0 {
+            return None;
+        }
+
+        self.pending
+            .retain(|_, pending| pending.first_seen.elapsed() < REASSEMBLY_TIMEOUT);
+
+        let key = (source, message_id);
+        let pending = self
+            .pending
+            .entry(key)
+            .or_insert_with(|| PendingReassembly {
+                fragments: HashMap::new(),
+                fragment_count,
+                first_seen: Instant::now(),
+            });
+        pending.fragments.insert(index, chunk.to_vec());
+
+        if pending.fragments.len() < pending.fragment_count as usize {
+            return None;
+        }
+
+        let pending = self.pending.remove(&key).unwrap();
+        let mut payload = Vec::new();
+        for index in 0..pending.fragment_count {
+            payload.extend(pending.fragments.get(&index)?);
+        }
+        Some(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:12345".parse().unwrap()
+    }
+
+    #[test]
+    fn split_payload_below_mtu_is_not_fragmented() {
+        let datagrams = split_payload(&[1, 2, 3], 1200);
+        assert_eq!(datagrams.len(), 1);
+        assert_eq!(datagrams[0], vec![MARKER_WHOLE, 1, 2, 3]);
+    }
+
+    #[test]
+    fn roundtrips_a_whole_payload() {
+        let mut table = ReassemblyTable::default();
+        let datagrams = split_payload(&[1, 2, 3], 1200);
+        assert_eq!(table.receive(addr(), &datagrams[0]), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn roundtrips_a_fragmented_payload_delivered_in_order() {
+        let mut table = ReassemblyTable::default();
+        let payload: Vec<u8> = (0..10).collect();
+        let datagrams = split_payload(&payload, 3);
+        assert!(datagrams.len() > 1);
+
+        let mut reassembled = None;
+        for datagram in &datagrams {
+            reassembled = table.receive(addr(), datagram);
+        }
+        assert_eq!(reassembled, Some(payload));
+    }
+
+    #[test]
+    fn roundtrips_a_fragmented_payload_delivered_out_of_order() {
+        let mut table = ReassemblyTable::default();
+        let payload: Vec<u8> = (0..10).collect();
+        let mut datagrams = split_payload(&payload, 3);
+        datagrams.reverse();
+
+        let mut reassembled = None;
+        for datagram in &datagrams {
+            reassembled = table.receive(addr(), datagram);
+        }
+        assert_eq!(reassembled, Some(payload));
+    }
+
+    #[test]
+    fn a_fragment_claiming_zero_fragment_count_is_rejected() {
+        let mut table = ReassemblyTable::default();
+        let mut datagram = vec![MARKER_FRAGMENT];
+        datagram.extend(1u32.to_le_bytes()); // message id
+        datagram.extend(0u16.to_le_bytes()); // index
+        datagram.extend(0u16.to_le_bytes()); // fragment_count = 0
+        datagram.extend([1, 2, 3]);
+
+        assert_eq!(table.receive(addr(), &datagram), None);
+        assert!(table.pending.is_empty());
+    }
+
+    #[test]
+    fn duplicate_fragments_do_not_complete_early() {
+        let mut table = ReassemblyTable::default();
+        let payload: Vec<u8> = (0..10).collect();
+        let datagrams = split_payload(&payload, 3);
+
+        // Feed the first fragment twice before ever sending the rest.
+        assert_eq!(table.receive(addr(), &datagrams[0]), None);
+        assert_eq!(table.receive(addr(), &datagrams[0]), None);
+
+        let mut reassembled = None;
+        for datagram in &datagrams[1..] {
+            reassembled = table.receive(addr(), datagram);
+        }
+        assert_eq!(reassembled, Some(payload));
+    }
+
+    #[test]
+    fn different_sources_reassemble_independently() {
+        let mut table = ReassemblyTable::default();
+        let payload_a: Vec<u8> = (0..10).collect();
+        let payload_b: Vec<u8> = (10..20).collect();
+        let datagrams_a = split_payload(&payload_a, 3);
+        let datagrams_b = split_payload(&payload_b, 3);
+        let other_addr: SocketAddr = "127.0.0.1:54321".parse().unwrap();
+
+        for datagram in &datagrams_a[..datagrams_a.len() - 1] {
+            assert_eq!(table.receive(addr(), datagram), None);
+        }
+        for datagram in &datagrams_b[..datagrams_b.len() - 1] {
+            assert_eq!(table.receive(other_addr, datagram), None);
+        }
+
+        let last_a = table.receive(addr(), datagrams_a.last().unwrap());
+        let last_b = table.receive(other_addr, datagrams_b.last().unwrap());
+        assert_eq!(last_a, Some(payload_a));
+        assert_eq!(last_b, Some(payload_b));
+    }
+
+    #[test]
+    fn stale_reassemblies_are_evicted_before_completing() {
+        let mut table = ReassemblyTable::default();
+        let payload: Vec<u8> = (0..10).collect();
+        let datagrams = split_payload(&payload, 3);
+
+        // Start a reassembly, then mark it old enough to be pruned.
+        table.receive(addr(), &datagrams[0]);
+        table
+            .pending
+            .values_mut()
+            .for_each(|pending| pending.first_seen -= REASSEMBLY_TIMEOUT * 2);
+
+        // Feeding the rest sweeps the expired entry first (losing fragment 0), so this
+        // reassembly can never complete even once every remaining fragment has arrived.
+        let mut reassembled = None;
+        for datagram in &datagrams[1..] {
+            reassembled = table.receive(addr(), datagram);
+        }
+        assert_eq!(reassembled, None);
+    }
+}
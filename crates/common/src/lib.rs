@@ -5,6 +5,27 @@ use async_net::{SocketAddr, UdpSocket};
 use flume::{Receiver, Sender, TryRecvError, TrySendError};
 use futures_lite::future;
 
+mod connection;
+mod dispatch;
+mod fragment;
+mod packet;
+mod reliability;
+mod stats;
+
+pub use connection::{
+    Connection, ConnectionEvent, DEFAULT_CONNECTION_TIMEOUT, DEFAULT_HEARTBEAT_INTERVAL,
+};
+pub use fragment::DEFAULT_FRAGMENT_MTU;
+pub use packet::{Packet, PacketEvent, SendPacketError};
+pub use reliability::{DeliveryMode, Reliability};
+pub use stats::NetworkStats;
+
+use fragment::ReassemblyTable;
+use stats::StatsHandle;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
 const BUFFER_MAX_SIZE: usize = 2000;
 
 /// An async-ready channel for communication between async tasks and bevy systems.
@@ -49,37 +70,107 @@ pub struct Network {
     recv_task: Option<Task<()>>,
     send_channel: AsyncChannel<Message>,
     recv_channel: AsyncChannel<Message>,
+    // Raw application traffic, handed off by the dispatch system after peeling off its channel
+    // tag. `try_recv` reads from here instead of `recv_channel` directly, so it keeps working
+    // no matter which other subsystems (typed packets, reliability, connections) are also
+    // dispatching off the same underlying channel.
+    raw_queue: Mutex<VecDeque<Message>>,
+    fragment_mtu: usize,
+    // Only populated when `NetworkPlugin::with_direct_send` is set; lets `try_send` write
+    // straight to the socket instead of handing off to `send_loop` over the send channel.
+    direct_socket: Option<UdpSocket>,
+    stats: StatsHandle,
 }
 
 impl Network {
-    pub fn new() -> Self {
+    pub fn new(fragment_mtu: usize) -> Self {
         Self {
             send_task: None,
             recv_task: None,
             send_channel: AsyncChannel::new(),
             recv_channel: AsyncChannel::new(),
+            raw_queue: Mutex::new(VecDeque::new()),
+            fragment_mtu,
+            direct_socket: None,
+            stats: StatsHandle::default(),
         }
     }
 
+    /// Pops the next raw application message, once the dispatch system has routed it here.
     pub fn try_recv(&self) -> Result<Message, TryRecvError> {
-        self.recv_channel.receiver.try_recv()
+        self.raw_queue
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(TryRecvError::Empty)
     }
 
+    /// Pulls the next message straight off the receive channel. Only meant for the dispatch
+    /// system; every other consumer should go through `try_recv` or a subsystem's own queue.
+    pub(crate) fn poll_channel(&self) -> Option<Message> {
+        self.recv_channel.receiver.try_recv().ok()
+    }
+
+    /// Queues a raw application message for `try_recv`. Only meant for the dispatch system.
+    pub(crate) fn push_raw(&self, message: Message) {
+        self.raw_queue.lock().unwrap().push_back(message);
+    }
+
+    /// Sends `message` on the raw channel read back out by `try_recv`. By default
+    /// this hands off to the `send_loop` task over a channel; if direct sends are enabled, it
+    /// instead writes to the socket inline via `block_on`, trading the channel hop and task
+    /// wakeup for blocking the calling system until the write completes.
     pub fn try_send(&self, message: Message) -> Result<(), TrySendError<Message>> {
-        self.send_channel.sender.try_send(message)
+        self.try_send_tagged(message.address, dispatch::CHANNEL_RAW, message.payload)
+    }
+
+    /// Sends `payload` to `address` on the given dispatch channel. Used internally by
+    /// subsystems (typed packets, reliability, connection tracking) to tag their traffic so
+    /// the shared dispatch system can route it without racing `try_recv` or each other.
+    pub(crate) fn try_send_tagged(
+        &self,
+        address: SocketAddr,
+        channel: u8,
+        payload: Vec<u8>,
+    ) -> Result<(), TrySendError<Message>> {
+        let message = Message::new(address, dispatch::wrap(channel, payload));
+        match &self.direct_socket {
+            Some(socket) => {
+                future::block_on(send_message(
+                    socket,
+                    &message,
+                    self.fragment_mtu,
+                    &self.stats,
+                ));
+                Ok(())
+            }
+            None => self.send_channel.sender.try_send(message),
+        }
     }
 
-    pub fn startup(&mut self, port: u16, runtime: &TaskPool) {
+    pub fn startup(&mut self, port: u16, runtime: &TaskPool, direct_send: bool) {
         let socket = bind_socket(port);
-        let send_relay = self.send_channel.receiver.clone();
         let recv_relay = self.recv_channel.sender.clone();
-        self.send_task = Some(runtime.spawn(send_loop(socket.clone(), send_relay)));
-        self.recv_task = Some(runtime.spawn(recv_loop(socket.clone(), recv_relay)));
+        let recv_stats = self.stats.clone();
+        self.recv_task = Some(runtime.spawn(recv_loop(socket.clone(), recv_relay, recv_stats)));
+
+        if direct_send {
+            self.direct_socket = Some(socket);
+        } else {
+            let send_relay = self.send_channel.receiver.clone();
+            let mtu = self.fragment_mtu;
+            let send_stats = self.stats.clone();
+            self.send_task = Some(runtime.spawn(send_loop(socket, send_relay, mtu, send_stats)));
+        }
     }
 
     pub fn parse_socket_addr(addr: &str) -> SocketAddr {
         addr.parse().unwrap()
     }
+
+    pub(crate) fn stats_handle(&self) -> StatsHandle {
+        self.stats.clone()
+    }
 }
 
 /// Binds a socket to the given port, using the local host address.
@@ -93,10 +184,15 @@ fn bind_socket(port: u16) -> UdpSocket {
 
 /// A network loop that awaits the next packet received by the UdpSocket
 /// add adds it to the incoming message queue for consumption within bevy.
-async fn send_loop(socket: UdpSocket, send_channel: Receiver<Message>) {
+async fn send_loop(
+    socket: UdpSocket,
+    send_channel: Receiver<Message>,
+    fragment_mtu: usize,
+    stats: StatsHandle,
+) {
     loop {
         match send_channel.recv_async().await {
-            Ok(message) => send_message(&socket, &message).await,
+            Ok(message) => send_message(&socket, &message, fragment_mtu, &stats).await,
             Err(err) => warn!("failed to dequeue outgoing message: {:?}", err),
         };
     }
@@ -104,11 +200,14 @@ async fn send_loop(socket: UdpSocket, send_channel: Receiver<Message>) {
 
 /// A network loop that awaits for a new message to be queued in the
 /// outgoing message queue from bevy, and sends it via the given socket.
-async fn recv_loop(socket: UdpSocket, recv_channel: Sender<Message>) {
+async fn recv_loop(socket: UdpSocket, recv_channel: Sender<Message>, stats: StatsHandle) {
     let mut buf = [0; BUFFER_MAX_SIZE];
+    let mut reassembly = ReassemblyTable::default();
     loop {
         match socket.recv_from(&mut buf).await {
-            Ok((amt, src)) => recv_message(&src, &buf[..amt], &recv_channel).await,
+            Ok((amt, src)) => {
+                recv_message(&src, &buf[..amt], &recv_channel, &mut reassembly, &stats).await
+            }
             Err(err) => match err.kind() {
                 std::io::ErrorKind::ConnectionReset => (), // Ignore ECONNRESET spam on recv
                 _ => warn!("failed to recv packet: {:?}", err),
@@ -117,16 +216,38 @@ async fn recv_loop(socket: UdpSocket, recv_channel: Sender<Message>) {
     }
 }
 
-/// Send a message out on a given UdpSocket. Uses the message address field as the target.
-async fn send_message(socket: &UdpSocket, message: &Message) {
-    if let Err(err) = socket.send_to(&message.payload, message.address).await {
-        warn!("failed to send packet to {}: {:?}", message.address, err);
+/// Sends a message out on a given UdpSocket, splitting it into fragments if it's larger
+/// than `fragment_mtu`. Uses the message address field as the target.
+async fn send_message(
+    socket: &UdpSocket,
+    message: &Message,
+    fragment_mtu: usize,
+    stats: &StatsHandle,
+) {
+    for datagram in fragment::split_payload(&message.payload, fragment_mtu) {
+        match socket.send_to(&datagram, message.address).await {
+            Ok(sent) => stats.record_sent(message.address, sent),
+            Err(err) => warn!("failed to send packet to {}: {:?}", message.address, err),
+        }
     }
 }
 
-/// Receives a message from the given address and adds it into the incoming message queue.
-async fn recv_message(source: &SocketAddr, buf: &[u8], recv_channel: &Sender<Message>) {
-    if let Err(err) = recv_channel.send(Message::new(*source, buf.to_vec())) {
+/// Receives a datagram from the given address, reassembling it if it's one of several
+/// fragments, and adds the completed message into the incoming message queue.
+async fn recv_message(
+    source: &SocketAddr,
+    buf: &[u8],
+    recv_channel: &Sender<Message>,
+    reassembly: &mut ReassemblyTable,
+    stats: &StatsHandle,
+) {
+    stats.record_received(*source, buf.len());
+
+    let Some(payload) = reassembly.receive(*source, buf) else {
+        return;
+    };
+
+    if let Err(err) = recv_channel.send(Message::new(*source, payload)) {
         warn!("failed to enqueue incoming message: {:?}", err);
     }
 }
@@ -134,11 +255,37 @@ async fn recv_message(source: &SocketAddr, buf: &[u8], recv_channel: &Sender<Mes
 /// Simple plugin for adding necessary network resources and functions.
 pub struct NetworkPlugin {
     listen_port: u16,
+    fragment_mtu: usize,
+    heartbeat_interval: Duration,
+    connection_timeout: Duration,
+    direct_send: bool,
+    packet_registrations: Vec<Box<dyn Fn(&mut App) + Send + Sync>>,
 }
 
 impl NetworkPlugin {
     pub fn new(listen_port: u16) -> Self {
-        NetworkPlugin { listen_port }
+        NetworkPlugin {
+            listen_port,
+            fragment_mtu: DEFAULT_FRAGMENT_MTU,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            connection_timeout: DEFAULT_CONNECTION_TIMEOUT,
+            direct_send: false,
+            packet_registrations: Vec::new(),
+        }
+    }
+
+    /// Overrides the maximum datagram size before a message is split into fragments.
+    pub fn with_fragment_mtu(mut self, fragment_mtu: usize) -> Self {
+        self.fragment_mtu = fragment_mtu;
+        self
+    }
+
+    /// Sends packets inline from the calling system instead of handing them off to the
+    /// send task over a channel. Lower latency for high send rates, at the cost of blocking
+    /// the calling system on the socket write.
+    pub fn with_direct_send(mut self) -> Self {
+        self.direct_send = true;
+        self
     }
 }
 
@@ -146,12 +293,26 @@ impl Plugin for NetworkPlugin {
     fn build(&self, app: &mut App) {
         // Add a startup system that incorporates the listen port we selected
         let port = self.listen_port;
+        let direct_send = self.direct_send;
         let startup = move |mut net: ResMut<Network>, runtime: Res<TaskPool>| {
-            net.startup(port, runtime.into_inner())
+            net.startup(port, runtime.into_inner(), direct_send)
         };
 
+        let network = Network::new(self.fragment_mtu);
+        let stats_handle = network.stats_handle();
+
         app.init_resource::<TaskPool>()
-            .insert_resource(Network::new())
+            .insert_resource(network)
             .add_startup_system(startup);
+
+        // Installed unconditionally: the single consumer of `Network`'s receive channel that
+        // every other subsystem below routes its traffic through, instead of draining the
+        // channel itself.
+        self.build_dispatch(app);
+
+        self.build_packets(app);
+        self.build_reliability(app);
+        self.build_connections(app);
+        self.build_stats(app, stats_handle);
     }
 }
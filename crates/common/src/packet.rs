@@ -0,0 +1,152 @@
+use std::any::type_name;
+use std::collections::HashMap;
+
+use bevy::ecs::world::World;
+use bevy::prelude::*;
+
+use async_net::SocketAddr;
+use flume::TrySendError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::dispatch::CHANNEL_PACKET;
+use crate::{Message, Network, NetworkPlugin};
+
+/// Marker trait for types that can be sent and received as typed network packets.
+///
+/// Implement it with [`impl_packet!`] rather than by hand.
+pub trait Packet: Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+/// Implements [`Packet`] for a type that already derives `Serialize`/`Deserialize`.
+#[macro_export]
+macro_rules! impl_packet {
+    ($ty:ty) => {
+        impl $crate::Packet for $ty {}
+    };
+}
+
+/// Event fired when a registered [`Packet`] type is received over the network.
+pub struct PacketEvent<T: Packet> {
+    packet: T,
+    address: SocketAddr,
+}
+
+impl<T: Packet> PacketEvent<T> {
+    pub fn packet(&self) -> &T {
+        &self.packet
+    }
+
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+}
+
+/// Number of bytes used by the type-tag header prepended to every encoded packet.
+const PACKET_TAG_SIZE: usize = std::mem::size_of::<u32>();
+
+/// Derives a stable wire tag for a packet type from its type name.
+///
+/// Both ends of a connection must be built against the same `T` for tags to line up.
+fn packet_tag<T: 'static>() -> u32 {
+    // FNV-1a over the type name; good enough to avoid collisions between a handful of types.
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in type_name::<T>().bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash
+}
+
+/// Bincode-encodes `packet` with a leading type-tag header.
+fn encode_packet<T: Packet>(packet: &T) -> Result<Vec<u8>, bincode::Error> {
+    let mut payload = packet_tag::<T>().to_le_bytes().to_vec();
+    payload.extend(bincode::serialize(packet)?);
+    Ok(payload)
+}
+
+/// Error returned by [`Network::send_packet`]: either `T` failed to encode, or the encoded
+/// payload failed to queue for sending.
+#[derive(Debug)]
+pub enum SendPacketError {
+    Encode(bincode::Error),
+    Send(TrySendError<Message>),
+}
+
+impl Network {
+    /// Bincode-encodes `packet` with a type-tag header and queues it for sending to `address`.
+    ///
+    /// Pairs with [`NetworkPlugin::register_packet`] on the receiving end.
+    pub fn send_packet<T: Packet>(
+        &self,
+        address: SocketAddr,
+        packet: &T,
+    ) -> Result<(), SendPacketError> {
+        let payload = encode_packet(packet).map_err(|err| {
+            warn!("failed to encode packet {}: {:?}", type_name::<T>(), err);
+            SendPacketError::Encode(err)
+        })?;
+        self.try_send_tagged(address, CHANNEL_PACKET, payload)
+            .map_err(SendPacketError::Send)
+    }
+}
+
+/// Decodes a tagged payload and fires it as a [`PacketEvent<T>`] into the world.
+type PacketDispatch = Box<dyn Fn(&mut World, SocketAddr, &[u8]) + Send + Sync>;
+
+/// Maps wire tags to the decode-and-fire closure for each registered packet type.
+#[derive(Default)]
+struct PacketRegistry {
+    dispatch: HashMap<u32, PacketDispatch>,
+}
+
+fn register_dispatch<T: Packet>(registry: &mut PacketRegistry) {
+    registry.dispatch.insert(
+        packet_tag::<T>(),
+        Box::new(
+            |world, address, body| match bincode::deserialize::<T>(body) {
+                Ok(packet) => world
+                    .resource_mut::<Events<PacketEvent<T>>>()
+                    .send(PacketEvent { packet, address }),
+                Err(err) => warn!("failed to decode packet {}: {:?}", type_name::<T>(), err),
+            },
+        ),
+    );
+}
+
+/// Routes a single message dispatched on the packet channel: decodes its type tag and fires
+/// the matching [`PacketEvent<T>`], if `T` was registered. Unregistered tags, or a body too
+/// short to carry one, are silently dropped — there is nothing else to route them to.
+pub(crate) fn route(world: &mut World, address: SocketAddr, body: &[u8]) {
+    if body.len() < PACKET_TAG_SIZE {
+        return;
+    }
+
+    let (tag_bytes, payload) = body.split_at(PACKET_TAG_SIZE);
+    let tag = u32::from_le_bytes(tag_bytes.try_into().unwrap());
+
+    world.resource_scope(|world, registry: Mut<PacketRegistry>| {
+        if let Some(dispatch) = registry.dispatch.get(&tag) {
+            dispatch(world, address, payload);
+        }
+    });
+}
+
+impl NetworkPlugin {
+    /// Registers `T` as a typed packet, wiring up its [`PacketEvent<T>`].
+    pub fn register_packet<T: Packet>(mut self) -> Self {
+        self.packet_registrations.push(Box::new(|app: &mut App| {
+            app.add_event::<PacketEvent<T>>();
+            let mut registry = app.world.resource_mut::<PacketRegistry>();
+            register_dispatch::<T>(&mut registry);
+        }));
+        self
+    }
+
+    pub(crate) fn build_packets(&self, app: &mut App) {
+        app.insert_resource(PacketRegistry::default());
+
+        for registration in &self.packet_registrations {
+            registration(app);
+        }
+    }
+}
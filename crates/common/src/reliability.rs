@@ -0,0 +1,471 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use bevy::ecs::world::World;
+use bevy::prelude::*;
+
+use async_net::SocketAddr;
+
+use crate::dispatch::CHANNEL_RELIABILITY;
+use crate::{Message, Network, NetworkPlugin};
+
+/// Delivery guarantee requested for a message sent via [`Reliability::send`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DeliveryMode {
+    /// Sent at most once, with no retransmission or ordering guarantee. Raw sends via
+    /// [`Network::try_send`] skip the reliability header and remain the cheaper option when
+    /// acks aren't needed at all.
+    Unreliable,
+    /// Resent until acked, but may be delivered out of order relative to other messages.
+    ReliableUnordered,
+    /// Resent until acked, and delivered in the order it was sent.
+    ReliableOrdered,
+}
+
+/// Size in bytes of the header prepended to every message sent through the reliability layer.
+const HEADER_SIZE: usize = 2 + 2 + 4 + 1;
+
+const FLAG_RELIABLE: u8 = 0b0000_0001;
+const FLAG_ORDERED: u8 = 0b0000_0010;
+// Set when `ack`/`ack_bitfield` carry a real ack, distinguishing "nothing received from this
+// peer yet" from "acking sequence 0", which is a perfectly legitimate sequence number.
+const FLAG_HAS_ACK: u8 = 0b0000_0100;
+
+const ACK_WINDOW: u32 = 32;
+const MIN_RESEND_TIMEOUT: Duration = Duration::from_millis(100);
+const MAX_RESEND_TIMEOUT: Duration = Duration::from_secs(3);
+const INITIAL_RTT: Duration = Duration::from_millis(200);
+
+/// Sequence/ack header for the reliability layer, piggybacking the sender's ack state on
+/// every outgoing packet so no separate ack-only datagrams are needed.
+struct ReliabilityHeader {
+    sequence: u16,
+    ack: u16,
+    ack_bitfield: u32,
+    flags: u8,
+}
+
+impl ReliabilityHeader {
+    /// `ack` is `None` until the sender has received anything at all from the peer — if it
+    /// were instead defaulted to sequence 0, a genuine ack of sequence 0 would be
+    /// indistinguishable from "no ack yet" and never get retransmitted correctly.
+    fn new(mode: DeliveryMode, sequence: u16, ack: Option<(u16, u32)>) -> Self {
+        let mut flags = 0;
+        if mode != DeliveryMode::Unreliable {
+            flags |= FLAG_RELIABLE;
+        }
+        if mode == DeliveryMode::ReliableOrdered {
+            flags |= FLAG_ORDERED;
+        }
+
+        let (ack, ack_bitfield) = match ack {
+            Some((ack, ack_bitfield)) => {
+                flags |= FLAG_HAS_ACK;
+                (ack, ack_bitfield)
+            }
+            None => (0, 0),
+        };
+
+        Self {
+            sequence,
+            ack,
+            ack_bitfield,
+            flags,
+        }
+    }
+
+    fn mode(&self) -> DeliveryMode {
+        if self.flags & FLAG_RELIABLE == 0 {
+            DeliveryMode::Unreliable
+        } else if self.flags & FLAG_ORDERED != 0 {
+            DeliveryMode::ReliableOrdered
+        } else {
+            DeliveryMode::ReliableUnordered
+        }
+    }
+
+    /// Returns the carried ack, or `None` if the sender hadn't received anything yet.
+    fn ack(&self) -> Option<(u16, u32)> {
+        (self.flags & FLAG_HAS_ACK != 0).then_some((self.ack, self.ack_bitfield))
+    }
+
+    fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(HEADER_SIZE + payload.len());
+        buf.extend(self.sequence.to_le_bytes());
+        buf.extend(self.ack.to_le_bytes());
+        buf.extend(self.ack_bitfield.to_le_bytes());
+        buf.push(self.flags);
+        buf.extend_from_slice(payload);
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Option<(Self, &[u8])> {
+        if buf.len() < HEADER_SIZE {
+            return None;
+        }
+        let sequence = u16::from_le_bytes(buf[0..2].try_into().unwrap());
+        let ack = u16::from_le_bytes(buf[2..4].try_into().unwrap());
+        let ack_bitfield = u32::from_le_bytes(buf[4..8].try_into().unwrap());
+        let flags = buf[8];
+        Some((
+            Self {
+                sequence,
+                ack,
+                ack_bitfield,
+                flags,
+            },
+            &buf[HEADER_SIZE..],
+        ))
+    }
+}
+
+/// Returns true if sequence `a` is newer than `b`, accounting for `u16` wraparound.
+fn sequence_greater(a: u16, b: u16) -> bool {
+    let diff = a.wrapping_sub(b);
+    diff != 0 && diff < 0x8000
+}
+
+struct UnackedPacket {
+    mode: DeliveryMode,
+    payload: Vec<u8>,
+    sent_at: Instant,
+}
+
+/// Per-peer sequencing, ack, and reorder state for the reliability layer.
+#[derive(Default)]
+struct PeerState {
+    next_sequence: u16,
+    unacked: HashMap<u16, UnackedPacket>,
+
+    highest_received: Option<u16>,
+    received_bitfield: u32,
+
+    next_expected: u16,
+    reorder: BTreeMap<u16, Vec<u8>>,
+
+    smoothed_rtt: Option<Duration>,
+}
+
+impl PeerState {
+    /// Records an incoming sequence, returning `false` if it's a duplicate we've already seen.
+    fn record_received(&mut self, sequence: u16) -> bool {
+        match self.highest_received {
+            None => {
+                self.highest_received = Some(sequence);
+                self.received_bitfield = 0;
+                true
+            }
+            Some(highest) if sequence == highest => false,
+            Some(highest) if sequence_greater(sequence, highest) => {
+                let shift = sequence.wrapping_sub(highest) as u32;
+                self.received_bitfield = if shift >= ACK_WINDOW {
+                    0
+                } else {
+                    (self.received_bitfield << shift) | (1 << (shift - 1))
+                };
+                self.highest_received = Some(sequence);
+                true
+            }
+            Some(highest) => {
+                let shift = highest.wrapping_sub(sequence) as u32;
+                if shift == 0 || shift >= ACK_WINDOW {
+                    false
+                } else {
+                    let bit = 1 << (shift - 1);
+                    let duplicate = self.received_bitfield & bit != 0;
+                    self.received_bitfield |= bit;
+                    !duplicate
+                }
+            }
+        }
+    }
+
+    /// Clears unacked packets the peer has now confirmed, folding the round trip time of each
+    /// into the smoothed RTT estimate used to scale resend timeouts.
+    fn apply_ack(&mut self, ack: u16, ack_bitfield: u32, now: Instant) {
+        let mut acked = vec![ack];
+        for bit in 0..ACK_WINDOW as u16 {
+            if ack_bitfield & (1 << bit as u32) != 0 {
+                acked.push(ack.wrapping_sub(bit + 1));
+            }
+        }
+
+        for sequence in acked {
+            if let Some(packet) = self.unacked.remove(&sequence) {
+                let rtt = now.saturating_duration_since(packet.sent_at);
+                self.smoothed_rtt = Some(match self.smoothed_rtt {
+                    Some(smoothed) if rtt >= smoothed => smoothed + (rtt - smoothed) / 8,
+                    Some(smoothed) => smoothed - (smoothed - rtt) / 8,
+                    None => rtt,
+                });
+            }
+        }
+    }
+
+    fn resend_timeout(&self) -> Duration {
+        (self.smoothed_rtt.unwrap_or(INITIAL_RTT) * 2).clamp(MIN_RESEND_TIMEOUT, MAX_RESEND_TIMEOUT)
+    }
+}
+
+/// Resource providing reliable/ordered delivery on top of [`Network`]'s raw unreliable sends.
+#[derive(Default)]
+pub struct Reliability {
+    peers: HashMap<SocketAddr, PeerState>,
+    inbound: VecDeque<Message>,
+}
+
+impl Reliability {
+    /// Queues `payload` for sending to `address` with the given [`DeliveryMode`].
+    pub fn send(
+        &mut self,
+        net: &Network,
+        address: SocketAddr,
+        mode: DeliveryMode,
+        payload: Vec<u8>,
+    ) {
+        let peer = self.peers.entry(address).or_default();
+
+        let sequence = peer.next_sequence;
+        peer.next_sequence = peer.next_sequence.wrapping_add(1);
+
+        let ack = peer
+            .highest_received
+            .map(|highest| (highest, peer.received_bitfield));
+        let header = ReliabilityHeader::new(mode, sequence, ack);
+        let encoded = header.encode(&payload);
+
+        if mode != DeliveryMode::Unreliable {
+            peer.unacked.insert(
+                sequence,
+                UnackedPacket {
+                    mode,
+                    payload,
+                    sent_at: Instant::now(),
+                },
+            );
+        }
+
+        if net
+            .try_send_tagged(address, CHANNEL_RELIABILITY, encoded)
+            .is_err()
+        {
+            warn!("failed to queue reliable packet to {}", address);
+        }
+    }
+
+    /// Pops the next message delivered by the reliability layer, in delivery order.
+    pub fn try_recv(&mut self) -> Option<Message> {
+        self.inbound.pop_front()
+    }
+
+    fn deliver(&mut self, address: SocketAddr, payload: Vec<u8>) {
+        self.inbound.push_back(Message::new(address, payload));
+    }
+}
+
+/// Routes a single message dispatched on the reliability channel: peels off the reliability
+/// header, acks the sender's outstanding packets, dedupes already-seen sequences, and reorders
+/// ordered-channel traffic.
+pub(crate) fn route(world: &mut World, address: SocketAddr, body: &[u8]) {
+    let Some((header, payload)) = ReliabilityHeader::decode(body) else {
+        return;
+    };
+
+    let now = Instant::now();
+    let mut reliability = world.resource_mut::<Reliability>();
+    let peer = reliability.peers.entry(address).or_default();
+
+    if let Some((ack, ack_bitfield)) = header.ack() {
+        peer.apply_ack(ack, ack_bitfield, now);
+    }
+
+    let mode = header.mode();
+    if mode != DeliveryMode::Unreliable && !peer.record_received(header.sequence) {
+        return; // duplicate, already delivered
+    }
+
+    if mode != DeliveryMode::ReliableOrdered {
+        reliability.deliver(address, payload.to_vec());
+        return;
+    }
+
+    let peer = reliability.peers.get_mut(&address).unwrap();
+    if header.sequence == peer.next_expected {
+        peer.next_expected = peer.next_expected.wrapping_add(1);
+        let mut deliverable = vec![payload.to_vec()];
+        while let Some(next) = peer.reorder.remove(&peer.next_expected) {
+            deliverable.push(next);
+            peer.next_expected = peer.next_expected.wrapping_add(1);
+        }
+        for payload in deliverable {
+            reliability.deliver(address, payload);
+        }
+    } else if sequence_greater(header.sequence, peer.next_expected) {
+        peer.reorder.insert(header.sequence, payload.to_vec());
+    } // else: already delivered, drop
+}
+
+/// Resends reliable packets that haven't been acked within an RTT-scaled timeout.
+fn resend_unacked(mut reliability: ResMut<Reliability>, net: Res<Network>) {
+    let now = Instant::now();
+
+    for (&address, peer) in reliability.peers.iter_mut() {
+        let timeout = peer.resend_timeout();
+        let ack = peer
+            .highest_received
+            .map(|highest| (highest, peer.received_bitfield));
+
+        for (&sequence, packet) in peer.unacked.iter_mut() {
+            if now.saturating_duration_since(packet.sent_at) < timeout {
+                continue;
+            }
+
+            let header = ReliabilityHeader::new(packet.mode, sequence, ack);
+            let encoded = header.encode(&packet.payload);
+            packet.sent_at = now;
+
+            if net
+                .try_send_tagged(address, CHANNEL_RELIABILITY, encoded)
+                .is_err()
+            {
+                warn!("failed to resend reliable packet to {}", address);
+            }
+        }
+    }
+}
+
+impl NetworkPlugin {
+    pub(crate) fn build_reliability(&self, app: &mut App) {
+        app.insert_resource(Reliability::default())
+            .add_system(resend_unacked);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_greater_handles_wraparound() {
+        assert!(sequence_greater(1, 0));
+        assert!(!sequence_greater(0, 1));
+        assert!(sequence_greater(0, 0xffff)); // wraps forward past u16::MAX
+        assert!(!sequence_greater(0xffff, 0));
+        assert!(!sequence_greater(5, 5)); // equal is not greater
+    }
+
+    #[test]
+    fn record_received_accepts_in_order_sequences() {
+        let mut peer = PeerState::default();
+        assert!(peer.record_received(0));
+        assert!(peer.record_received(1));
+        assert!(peer.record_received(2));
+        assert_eq!(peer.highest_received, Some(2));
+    }
+
+    #[test]
+    fn record_received_rejects_duplicates() {
+        let mut peer = PeerState::default();
+        assert!(peer.record_received(5));
+        assert!(!peer.record_received(5));
+    }
+
+    #[test]
+    fn record_received_accepts_late_arrivals_within_window() {
+        let mut peer = PeerState::default();
+        assert!(peer.record_received(10));
+        assert!(peer.record_received(9)); // arrived out of order, but still new
+        assert!(!peer.record_received(9)); // now a duplicate
+        assert_eq!(peer.highest_received, Some(10));
+    }
+
+    #[test]
+    fn record_received_rejects_arrivals_outside_window() {
+        let mut peer = PeerState::default();
+        assert!(peer.record_received(1000));
+        assert!(!peer.record_received(1000 - ACK_WINDOW as u16 - 1));
+    }
+
+    #[test]
+    fn record_received_handles_a_gap_of_exactly_the_ack_window() {
+        // A shift equal to ACK_WINDOW must reset the bitfield rather than shift a u32 by a
+        // full 32 bits, which overflows.
+        let mut peer = PeerState::default();
+        assert!(peer.record_received(0));
+        assert!(peer.record_received(ACK_WINDOW as u16));
+        assert_eq!(peer.received_bitfield, 0);
+    }
+
+    #[test]
+    fn apply_ack_clears_the_acked_packet_and_updates_rtt() {
+        let mut peer = PeerState::default();
+        let sent_at = Instant::now();
+        peer.unacked.insert(
+            3,
+            UnackedPacket {
+                mode: DeliveryMode::ReliableUnordered,
+                payload: vec![],
+                sent_at,
+            },
+        );
+
+        peer.apply_ack(3, 0, sent_at + Duration::from_millis(50));
+
+        assert!(!peer.unacked.contains_key(&3));
+        assert!(peer.smoothed_rtt.is_some());
+    }
+
+    #[test]
+    fn apply_ack_clears_packets_covered_by_the_bitfield() {
+        let mut peer = PeerState::default();
+        let sent_at = Instant::now();
+        for sequence in [5, 6, 7] {
+            peer.unacked.insert(
+                sequence,
+                UnackedPacket {
+                    mode: DeliveryMode::ReliableUnordered,
+                    payload: vec![],
+                    sent_at,
+                },
+            );
+        }
+
+        // ack = 7 with bits 0 and 1 set also confirms sequences 6 and 5.
+        peer.apply_ack(7, 0b11, sent_at + Duration::from_millis(10));
+
+        assert!(!peer.unacked.contains_key(&5));
+        assert!(!peer.unacked.contains_key(&6));
+        assert!(!peer.unacked.contains_key(&7));
+    }
+
+    #[test]
+    fn apply_ack_leaves_unrelated_packets_unacked() {
+        let mut peer = PeerState::default();
+        let sent_at = Instant::now();
+        peer.unacked.insert(
+            9,
+            UnackedPacket {
+                mode: DeliveryMode::ReliableUnordered,
+                payload: vec![],
+                sent_at,
+            },
+        );
+
+        peer.apply_ack(3, 0, sent_at);
+
+        assert!(peer.unacked.contains_key(&9));
+        assert!(peer.smoothed_rtt.is_none());
+    }
+
+    #[test]
+    fn header_ack_is_none_until_a_real_ack_is_carried() {
+        // Before anything has been received from a peer, `ReliabilityHeader::ack` returns
+        // `None` rather than a sentinel ack of 0 — a genuine sequence-0 packet must not be
+        // treated as already acked.
+        let header = ReliabilityHeader::new(DeliveryMode::ReliableUnordered, 0, None);
+        assert_eq!(header.ack(), None);
+
+        let header = ReliabilityHeader::new(DeliveryMode::ReliableUnordered, 0, Some((0, 0)));
+        assert_eq!(header.ack(), Some((0, 0)));
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+
+use async_net::SocketAddr;
+
+use crate::NetworkPlugin;
+
+/// Number of samples kept for the windowed bytes/sec rate views.
+const RATE_WINDOW: usize = 120;
+
+#[derive(Default)]
+struct Counter {
+    bytes: AtomicU64,
+    packets: AtomicU64,
+}
+
+impl Counter {
+    fn add(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    fn packets(&self) -> u64 {
+        self.packets.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Default)]
+struct PeerCounters {
+    sent: Counter,
+    received: Counter,
+}
+
+#[derive(Default)]
+struct StatsInner {
+    sent: Counter,
+    received: Counter,
+    peers: Mutex<HashMap<SocketAddr, PeerCounters>>,
+}
+
+/// Cheap, cloneable handle shared between the async send/recv tasks and the `NetworkStats`
+/// resource sampled by bevy systems.
+#[derive(Default, Clone)]
+pub(crate) struct StatsHandle(Arc<StatsInner>);
+
+impl StatsHandle {
+    pub(crate) fn record_sent(&self, address: SocketAddr, bytes: usize) {
+        self.0.sent.add(bytes);
+        self.0
+            .peers
+            .lock()
+            .unwrap()
+            .entry(address)
+            .or_default()
+            .sent
+            .add(bytes);
+    }
+
+    pub(crate) fn record_received(&self, address: SocketAddr, bytes: usize) {
+        self.0.received.add(bytes);
+        self.0
+            .peers
+            .lock()
+            .unwrap()
+            .entry(address)
+            .or_default()
+            .received
+            .add(bytes);
+    }
+}
+
+struct Sample {
+    elapsed: f64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+/// Resource exposing global and per-peer byte/packet counters, plus a windowed bytes/sec rate.
+pub struct NetworkStats {
+    handle: StatsHandle,
+    samples: VecDeque<Sample>,
+}
+
+impl NetworkStats {
+    pub(crate) fn new(handle: StatsHandle) -> Self {
+        Self {
+            handle,
+            samples: VecDeque::with_capacity(RATE_WINDOW),
+        }
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.handle.0.sent.bytes()
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.handle.0.received.bytes()
+    }
+
+    pub fn packets_sent(&self) -> u64 {
+        self.handle.0.sent.packets()
+    }
+
+    pub fn packets_received(&self) -> u64 {
+        self.handle.0.received.packets()
+    }
+
+    pub fn peer_bytes_sent(&self, address: &SocketAddr) -> u64 {
+        self.with_peer(address, |peer| peer.sent.bytes())
+    }
+
+    pub fn peer_bytes_received(&self, address: &SocketAddr) -> u64 {
+        self.with_peer(address, |peer| peer.received.bytes())
+    }
+
+    pub fn peer_packets_sent(&self, address: &SocketAddr) -> u64 {
+        self.with_peer(address, |peer| peer.sent.packets())
+    }
+
+    pub fn peer_packets_received(&self, address: &SocketAddr) -> u64 {
+        self.with_peer(address, |peer| peer.received.packets())
+    }
+
+    fn with_peer<T: Default>(&self, address: &SocketAddr, f: impl FnOnce(&PeerCounters) -> T) -> T {
+        self.handle
+            .0
+            .peers
+            .lock()
+            .unwrap()
+            .get(address)
+            .map(f)
+            .unwrap_or_default()
+    }
+
+    /// Average bytes/sec sent, over the last [`RATE_WINDOW`] samples.
+    pub fn send_rate(&self) -> f64 {
+        Self::rate(&self.samples, |sample| sample.bytes_sent)
+    }
+
+    /// Average bytes/sec received, over the last [`RATE_WINDOW`] samples.
+    pub fn recv_rate(&self) -> f64 {
+        Self::rate(&self.samples, |sample| sample.bytes_received)
+    }
+
+    fn rate(samples: &VecDeque<Sample>, bytes: impl Fn(&Sample) -> u64) -> f64 {
+        match (samples.front(), samples.back()) {
+            (Some(first), Some(last)) if last.elapsed > first.elapsed => {
+                (bytes(last) - bytes(first)) as f64 / (last.elapsed - first.elapsed)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn sample(&mut self, elapsed: f64) {
+        self.samples.push_back(Sample {
+            elapsed,
+            bytes_sent: self.bytes_sent(),
+            bytes_received: self.bytes_received(),
+        });
+        if self.samples.len() > RATE_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+}
+
+/// Pushes a new rate sample onto `NetworkStats` each frame.
+fn sample_stats(mut stats: ResMut<NetworkStats>, time: Res<Time>) {
+    stats.sample(time.elapsed_seconds_f64());
+}
+
+impl NetworkPlugin {
+    pub(crate) fn build_stats(&self, app: &mut App, handle: StatsHandle) {
+        app.insert_resource(NetworkStats::new(handle))
+            .add_system(sample_stats);
+    }
+}
@@ -1,14 +1,26 @@
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
 
-use common::{Message, Network, NetworkPlugin};
+use serde::{Deserialize, Serialize};
+
+use common::{impl_packet, Message, Network, NetworkPlugin, PacketEvent};
+
+/// A typed chat packet, demonstrating `NetworkPlugin::register_packet` alongside the raw
+/// `Network::try_recv`/`try_send` API used for the "message!"/"reply!" traffic below.
+#[derive(Serialize, Deserialize)]
+struct ChatMessage {
+    text: String,
+}
+
+impl_packet!(ChatMessage);
 
 fn main() {
     let mut app = App::new();
     app.add_plugins(MinimalPlugins);
     app.add_plugin(LogPlugin::default());
-    app.add_plugin(NetworkPlugin::new(34243)); // Set the listen port to 34243
+    app.add_plugin(NetworkPlugin::new(34243).register_packet::<ChatMessage>()); // Set the listen port to 34243
     app.add_system(print_network_messages);
+    app.add_system(echo_chat_messages);
     app.run();
 }
 
@@ -25,3 +37,20 @@ fn print_network_messages(net: Res<Network>) {
         }
     }
 }
+
+fn echo_chat_messages(net: Res<Network>, mut packets: EventReader<PacketEvent<ChatMessage>>) {
+    for packet in packets.iter() {
+        info!(
+            "got chat: \"{}\" from: {}",
+            packet.packet().text,
+            packet.address()
+        );
+
+        let reply = ChatMessage {
+            text: "hello from server".to_string(),
+        };
+        if net.send_packet(*packet.address(), &reply).is_err() {
+            warn!("failed to send chat reply");
+        }
+    }
+}